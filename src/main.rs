@@ -6,6 +6,9 @@ use std::{
 };
 
 use anyhow::{Error, Result};
+use num_complex::Complex64;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
 
 #[derive(Debug, Clone)]
 pub enum Token {
@@ -15,41 +18,212 @@ pub enum Token {
     Slash,
     LeftParen,
     RightParen,
-    Number(f32),
-    Variable(String), // never seen by parser
+    Number(Complex64),
+    Variable(String),
     Power,
-    Ln,
-    Log,
-    Sin,
-    Cos,
-    Tan,
+    Equal,
+    Semicolon,
+    Comma,
+    EqualEqual,
+    BangEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    AmpAmp,
+    PipePipe,
+}
+
+/// The runtime value domain: numbers for arithmetic, booleans for
+/// comparisons and the `if` conditional.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Num(Complex64),
+    Bool(bool),
+}
+
+impl Value {
+    fn as_num(self) -> Result<Complex64> {
+        match self {
+            Self::Num(n) => Ok(n),
+            Self::Bool(b) => Err(Error::msg(format!("expected a number, found bool {}", b))),
+        }
+    }
+
+    fn as_bool(self) -> Result<bool> {
+        match self {
+            Self::Bool(b) => Ok(b),
+            Self::Num(n) => Err(Error::msg(format!("expected a bool, found number {}", n))),
+        }
+    }
+}
+
+/// A callable bound in an `Environment`: either a native implementation
+/// (`sin`, `ln`, ...) or a user-defined `name(params) = body` function.
+#[derive(Debug, Clone)]
+enum Function {
+    Builtin(fn(Complex64) -> Complex64),
+    UserDefined(Vec<String>, ParseExpr),
+}
+
+fn log10(z: Complex64) -> Complex64 {
+    z.ln() / Complex64::new(10f64.ln(), 0.0)
+}
+
+/// Computes `base^exp`. `Complex::powc` round-trips through `exp(ln(z) * w)`,
+/// which loses a few ULPs even for plain integer powers like `2^3` — the
+/// common case for this calculator — so the all-real case is special-cased
+/// onto `f64::powi`/`powf` and only genuinely complex operands fall through
+/// to `powc`.
+fn complex_pow(base: Complex64, exp: Complex64) -> Complex64 {
+    if base.im == 0.0 && exp.im == 0.0 && (base.re >= 0.0 || exp.re.fract() == 0.0) {
+        if exp.re.fract() == 0.0 && exp.re.abs() <= i32::MAX as f64 {
+            return Complex64::new(base.re.powi(exp.re as i32), 0.0);
+        }
+        return Complex64::new(base.re.powf(exp.re), 0.0);
+    }
+    base.powc(exp)
+}
+
+/// Variable and function bindings that outlive a single `evaluate` call, so
+/// a REPL session can set `x` on one line and read it back on the next.
+#[derive(Debug, Clone)]
+pub struct Environment {
+    variables: HashMap<String, Complex64>,
+    functions: HashMap<String, Function>,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        let mut functions = HashMap::new();
+        functions.insert("sin".to_string(), Function::Builtin(Complex64::sin));
+        functions.insert("cos".to_string(), Function::Builtin(Complex64::cos));
+        functions.insert("tan".to_string(), Function::Builtin(Complex64::tan));
+        functions.insert("ln".to_string(), Function::Builtin(Complex64::ln));
+        functions.insert("log".to_string(), Function::Builtin(log10));
+        Self {
+            variables: HashMap::new(),
+            functions,
+        }
+    }
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Looks up a variable, falling back to an interactive stdin prompt (and
+    /// remembering the answer) when it hasn't been bound yet.
+    fn resolve(&mut self, name: &str) -> Result<Complex64> {
+        if let Some(value) = self.variables.get(name) {
+            return Ok(*value);
+        }
+        let mut var_value = String::new();
+        print!("Set value [{}]: ", name);
+        stdout().flush()?;
+        std::io::stdin().read_line(&mut var_value)?;
+        let parsed = Complex64::new(var_value.trim().parse::<f64>()?, 0.0);
+        self.variables.insert(name.to_string(), parsed);
+        Ok(parsed)
+    }
+
+    /// Builds the child scope a function call runs in: its parameters bound
+    /// to the evaluated arguments, sharing the parent's function table.
+    fn child_scope(&self, bindings: HashMap<String, Complex64>) -> Self {
+        Self {
+            variables: bindings,
+            functions: self.functions.clone(),
+        }
+    }
 }
 
 // expr -> term
 // term -> factor (("-" | "+") factor)*;
 // factor -> power ( ( "/" | "*") power )*;
 // power -> unary ( "^" unary)*;
-// unary -> ("-" | "+" | "ln") | primary;
-// primary -> Number | "(" expr ")"
+// unary -> ("-" | "+") unary | primary;
+// primary -> Number | Variable | Variable "(" (expr ("," expr)*)? ")" | "(" expr ")"
 
 #[derive(Debug, Clone)]
 pub enum ParseExpr {
     Binary(Box<ParseExpr>, Token, Box<ParseExpr>),
     Unary(Token, Box<ParseExpr>),
     Value(Token),
+    Call(String, Vec<ParseExpr>),
 }
 
 impl ParseExpr {
     fn expr<I>(tokens: &mut Peekable<I>) -> Result<Self>
     where
-        I: Iterator<Item = Token>,
+        I: Iterator<Item = Token> + Clone,
+    {
+        Ok(Self::logic_or(tokens)?)
+    }
+
+    fn logic_or<I>(tokens: &mut Peekable<I>) -> Result<Self>
+    where
+        I: Iterator<Item = Token> + Clone,
+    {
+        let mut expr = Self::logic_and(tokens)?;
+        while let Some(token) = tokens.peek() {
+            if matches!(token, Token::PipePipe) {
+                let op = tokens.next();
+                let right = Self::logic_and(tokens)?;
+                expr = Self::Binary(Box::new(expr), op.unwrap(), Box::new(right))
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    fn logic_and<I>(tokens: &mut Peekable<I>) -> Result<Self>
+    where
+        I: Iterator<Item = Token> + Clone,
+    {
+        let mut expr = Self::comparison(tokens)?;
+        while let Some(token) = tokens.peek() {
+            if matches!(token, Token::AmpAmp) {
+                let op = tokens.next();
+                let right = Self::comparison(tokens)?;
+                expr = Self::Binary(Box::new(expr), op.unwrap(), Box::new(right))
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    // Comparisons don't chain (`1 < 2 < 3` isn't meaningful once each side
+    // can be a bool), so this applies at most one operator, unlike the
+    // other precedence levels' `while` loops.
+    fn comparison<I>(tokens: &mut Peekable<I>) -> Result<Self>
+    where
+        I: Iterator<Item = Token> + Clone,
     {
-        Ok(Self::term(tokens)?)
+        let expr = Self::term(tokens)?;
+        if let Some(token) = tokens.peek() {
+            if matches!(
+                token,
+                Token::EqualEqual
+                    | Token::BangEqual
+                    | Token::Less
+                    | Token::LessEqual
+                    | Token::Greater
+                    | Token::GreaterEqual
+            ) {
+                let op = tokens.next();
+                let right = Self::term(tokens)?;
+                return Ok(Self::Binary(Box::new(expr), op.unwrap(), Box::new(right)));
+            }
+        }
+        Ok(expr)
     }
 
     fn term<I>(tokens: &mut Peekable<I>) -> Result<Self>
     where
-        I: Iterator<Item = Token>,
+        I: Iterator<Item = Token> + Clone,
     {
         let mut expr = Self::factor(tokens)?;
         while let Some(token) = tokens.peek() {
@@ -66,7 +240,7 @@ impl ParseExpr {
 
     fn factor<I>(tokens: &mut Peekable<I>) -> Result<Self>
     where
-        I: Iterator<Item = Token>,
+        I: Iterator<Item = Token> + Clone,
     {
         let mut expr = Self::power(tokens)?;
         while let Some(token) = tokens.peek() {
@@ -82,7 +256,7 @@ impl ParseExpr {
     }
     fn power<I>(tokens: &mut Peekable<I>) -> Result<Self>
     where
-        I: Iterator<Item = Token>,
+        I: Iterator<Item = Token> + Clone,
     {
         let mut expr = Self::unary(tokens)?;
         while let Some(token) = tokens.peek() {
@@ -99,19 +273,10 @@ impl ParseExpr {
 
     fn unary<I>(tokens: &mut Peekable<I>) -> Result<Self>
     where
-        I: Iterator<Item = Token>,
+        I: Iterator<Item = Token> + Clone,
     {
         if let Some(token) = tokens.peek() {
-            if matches!(
-                token,
-                Token::Minus
-                    | Token::Plus
-                    | Token::Ln
-                    | Token::Log
-                    | Token::Sin
-                    | Token::Cos
-                    | Token::Tan
-            ) {
+            if matches!(token, Token::Minus | Token::Plus) {
                 let op = tokens.next();
                 let right = Self::unary(tokens)?;
                 return Ok(Self::Unary(op.unwrap(), Box::new(right)));
@@ -122,11 +287,25 @@ impl ParseExpr {
 
     fn primary<I>(tokens: &mut Peekable<I>) -> Result<Self>
     where
-        I: Iterator<Item = Token>,
+        I: Iterator<Item = Token> + Clone,
     {
-        if let Some(token) = tokens.peek() {
+        if let Some(token) = tokens.peek().cloned() {
             match token {
-                Token::Number(_) => return Ok(Self::Value(tokens.next().unwrap())),
+                Token::Number(_) => {
+                    tokens.next();
+                    return Ok(Self::Value(token));
+                }
+                Token::Variable(name) => {
+                    let mut lookahead = tokens.clone();
+                    lookahead.next();
+                    if matches!(lookahead.peek(), Some(Token::LeftParen)) {
+                        tokens.next();
+                        tokens.next();
+                        return Self::call_arguments(tokens).map(|args| Self::Call(name, args));
+                    }
+                    tokens.next();
+                    return Ok(Self::Value(Token::Variable(name)));
+                }
                 Token::LeftParen => {
                     tokens.next();
                     let expr = Self::expr(tokens)?;
@@ -141,33 +320,259 @@ impl ParseExpr {
         Err(Error::msg("parser failed"))
     }
 
-    fn eval(&self) -> Result<f32> {
+    /// Parses a `,`-separated argument list up to and including the closing
+    /// `)`; the opening `(` has already been consumed by the caller.
+    fn call_arguments<I>(tokens: &mut Peekable<I>) -> Result<Vec<Self>>
+    where
+        I: Iterator<Item = Token> + Clone,
+    {
+        let mut args = vec![];
+        if !matches!(tokens.peek(), Some(Token::RightParen)) {
+            loop {
+                args.push(Self::expr(tokens)?);
+                if matches!(tokens.peek(), Some(Token::Comma)) {
+                    tokens.next();
+                } else {
+                    break;
+                }
+            }
+        }
+        if !matches!(tokens.next(), Some(Token::RightParen)) {
+            return Err(Error::msg("Expected ')' after call arguments"));
+        }
+        Ok(args)
+    }
+
+    fn eval(&self, env: &mut Environment) -> Result<Value> {
         Ok(match self {
             Self::Binary(left, o, right) => match o {
-                Token::Plus => left.eval()? + right.eval()?,
-                Token::Star => left.eval()? * right.eval()?,
-                Token::Slash => left.eval()? / right.eval()?,
-                Token::Minus => left.eval()? - right.eval()?,
-                Token::Power => left.eval()?.powf(right.eval()?),
+                Token::Plus => Value::Num(left.eval(env)?.as_num()? + right.eval(env)?.as_num()?),
+                Token::Star => Value::Num(left.eval(env)?.as_num()? * right.eval(env)?.as_num()?),
+                Token::Slash => {
+                    Value::Num(left.eval(env)?.as_num()? / right.eval(env)?.as_num()?)
+                }
+                Token::Minus => {
+                    Value::Num(left.eval(env)?.as_num()? - right.eval(env)?.as_num()?)
+                }
+                Token::Power => Value::Num(complex_pow(
+                    left.eval(env)?.as_num()?,
+                    right.eval(env)?.as_num()?,
+                )),
+                Token::EqualEqual => Value::Bool(left.eval(env)? == right.eval(env)?),
+                Token::BangEqual => Value::Bool(left.eval(env)? != right.eval(env)?),
+                // Complex numbers have no total order, so comparisons are
+                // defined over the real part only.
+                Token::Less => {
+                    Value::Bool(left.eval(env)?.as_num()?.re < right.eval(env)?.as_num()?.re)
+                }
+                Token::LessEqual => {
+                    Value::Bool(left.eval(env)?.as_num()?.re <= right.eval(env)?.as_num()?.re)
+                }
+                Token::Greater => {
+                    Value::Bool(left.eval(env)?.as_num()?.re > right.eval(env)?.as_num()?.re)
+                }
+                Token::GreaterEqual => {
+                    Value::Bool(left.eval(env)?.as_num()?.re >= right.eval(env)?.as_num()?.re)
+                }
+                // Short-circuiting: the right side is only evaluated when it
+                // can actually change the result.
+                Token::AmpAmp => {
+                    Value::Bool(left.eval(env)?.as_bool()? && right.eval(env)?.as_bool()?)
+                }
+                Token::PipePipe => {
+                    Value::Bool(left.eval(env)?.as_bool()? || right.eval(env)?.as_bool()?)
+                }
                 _ => return Err(Error::msg("Invalid binary operand.")),
             },
             Self::Unary(o, expr) => match o {
-                Token::Minus => -expr.eval()?,
-                Token::Plus => expr.eval()?,
-                Token::Sin => expr.eval()?.sin(),
-                Token::Cos => expr.eval()?.cos(),
-                Token::Tan => expr.eval()?.tan(),
-                Token::Ln => expr.eval()?.ln(),
-                Token::Log => expr.eval()?.log10(),
+                // `Complex`'s `Neg` negates component-wise, so negating a real
+                // literal (`im: 0.0`) produces `im: -0.0`. That flips which
+                // side of `ln`'s branch cut `atan2` lands on, so normalize
+                // the sign back before it can affect anything downstream.
+                Token::Minus => {
+                    let mut negated = -expr.eval(env)?.as_num()?;
+                    if negated.im == 0.0 {
+                        negated.im = 0.0;
+                    }
+                    Value::Num(negated)
+                }
+                Token::Plus => Value::Num(expr.eval(env)?.as_num()?),
                 _ => return Err(Error::msg("Invalid unary operand.")),
             },
             Self::Value(token) => match token {
-                Token::Number(n) => *n,
+                Token::Number(n) => Value::Num(*n),
+                Token::Variable(name) => Value::Num(env.resolve(name)?),
                 _ => return Err(Error::msg("Invalid value")),
             },
+            Self::Call(name, args) if name == "if" => {
+                if args.len() != 3 {
+                    return Err(Error::msg(format!(
+                        "'if' expects 3 arguments, got {}",
+                        args.len()
+                    )));
+                }
+                if args[0].eval(env)?.as_bool()? {
+                    args[1].eval(env)?
+                } else {
+                    args[2].eval(env)?
+                }
+            }
+            Self::Call(name, args) => {
+                let function = env
+                    .functions
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| Error::msg(format!("Unknown function '{}'", name)))?;
+                match function {
+                    Function::Builtin(f) => {
+                        if args.len() != 1 {
+                            return Err(Error::msg(format!(
+                                "'{}' expects 1 argument, got {}",
+                                name,
+                                args.len()
+                            )));
+                        }
+                        Value::Num(f(args[0].eval(env)?.as_num()?))
+                    }
+                    Function::UserDefined(params, body) => {
+                        if params.len() != args.len() {
+                            return Err(Error::msg(format!(
+                                "'{}' expects {} argument(s), got {}",
+                                name,
+                                params.len(),
+                                args.len()
+                            )));
+                        }
+                        let mut bindings = HashMap::new();
+                        for (param, arg) in params.iter().zip(args) {
+                            bindings.insert(param.clone(), arg.eval(env)?.as_num()?);
+                        }
+                        let mut child = env.child_scope(bindings);
+                        body.eval(&mut child)?
+                    }
+                }
+            }
         })
     }
 }
+
+/// A `;`-separated program is a list of statements evaluated in order
+/// against a shared `Environment`; the value of the last one is the result.
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Assign(String, ParseExpr),
+    FnDef(String, Vec<String>, ParseExpr),
+    Expr(ParseExpr),
+}
+
+impl Stmt {
+    fn parse<I>(tokens: &mut Peekable<I>) -> Result<Self>
+    where
+        I: Iterator<Item = Token> + Clone,
+    {
+        if let Some(stmt) = Self::try_parse_fn_def(tokens)? {
+            return Ok(stmt);
+        }
+
+        if let Some(Token::Variable(name)) = tokens.peek().cloned() {
+            let mut lookahead = tokens.clone();
+            lookahead.next();
+            if matches!(lookahead.peek(), Some(Token::Equal)) {
+                tokens.next();
+                tokens.next();
+                return Ok(Self::Assign(name, ParseExpr::expr(tokens)?));
+            }
+        }
+        Ok(Self::Expr(ParseExpr::expr(tokens)?))
+    }
+
+    /// Speculatively parses `name(param, ...) = body`. Runs on a cloned
+    /// cursor so a call expression like `f(3, 4)`, which shares the same
+    /// prefix, falls through untouched when the probe doesn't pan out.
+    fn try_parse_fn_def<I>(tokens: &mut Peekable<I>) -> Result<Option<Self>>
+    where
+        I: Iterator<Item = Token> + Clone,
+    {
+        let mut probe = tokens.clone();
+        let name = match probe.next() {
+            Some(Token::Variable(name)) => name,
+            _ => return Ok(None),
+        };
+        if !matches!(probe.peek(), Some(Token::LeftParen)) {
+            return Ok(None);
+        }
+        probe.next();
+
+        let mut params = vec![];
+        if !matches!(probe.peek(), Some(Token::RightParen)) {
+            loop {
+                match probe.next() {
+                    Some(Token::Variable(param)) => params.push(param),
+                    _ => return Ok(None),
+                }
+                if matches!(probe.peek(), Some(Token::Comma)) {
+                    probe.next();
+                } else {
+                    break;
+                }
+            }
+        }
+        if !matches!(probe.next(), Some(Token::RightParen)) {
+            return Ok(None);
+        }
+        if !matches!(probe.peek(), Some(Token::Equal)) {
+            return Ok(None);
+        }
+        probe.next();
+
+        let body = ParseExpr::expr(&mut probe)?;
+        *tokens = probe;
+        Ok(Some(Self::FnDef(name, params, body)))
+    }
+
+    fn eval(&self, env: &mut Environment) -> Result<Value> {
+        match self {
+            Self::Assign(name, expr) => {
+                let value = expr.eval(env)?.as_num()?;
+                env.variables.insert(name.clone(), value);
+                Ok(Value::Num(value))
+            }
+            Self::FnDef(name, params, body) => {
+                // `if` is a hardcoded ternary matched by name in `eval`, not a
+                // lookup against `env.functions`, so "defining" over it would
+                // silently do nothing instead of changing its behavior.
+                if name == "if" {
+                    return Err(Error::msg("'if' is reserved and cannot be redefined"));
+                }
+                env.functions.insert(
+                    name.clone(),
+                    Function::UserDefined(params.clone(), body.clone()),
+                );
+                Ok(Value::Num(Complex64::new(0.0, 0.0)))
+            }
+            Self::Expr(expr) => expr.eval(env),
+        }
+    }
+}
+
+fn parse_program<I>(tokens: I) -> Result<Vec<Stmt>>
+where
+    I: Iterator<Item = Token> + Clone,
+{
+    let mut tokens = tokens.peekable();
+    let mut stmts = vec![];
+    while tokens.peek().is_some() {
+        stmts.push(Stmt::parse(&mut tokens)?);
+        match tokens.peek() {
+            Some(Token::Semicolon) => {
+                tokens.next();
+            }
+            Some(_) => return Err(Error::msg("Expected ';' between statements")),
+            None => {}
+        }
+    }
+    Ok(stmts)
+}
 pub fn produce_tokens(expr: String) -> Result<Vec<Token>> {
     use Token::*;
 
@@ -180,20 +585,54 @@ pub fn produce_tokens(expr: String) -> Result<Vec<Token>> {
             '*' => tokens.push(Star),
             '/' => tokens.push(Slash),
             '^' => tokens.push(Power),
+            '=' => {
+                if chars.next_if_eq(&'=').is_some() {
+                    tokens.push(EqualEqual);
+                } else {
+                    tokens.push(Equal);
+                }
+            }
+            '!' => {
+                if chars.next_if_eq(&'=').is_some() {
+                    tokens.push(BangEqual);
+                } else {
+                    return Err(Error::msg("expected '=' after '!'"));
+                }
+            }
+            '<' => {
+                if chars.next_if_eq(&'=').is_some() {
+                    tokens.push(LessEqual);
+                } else {
+                    tokens.push(Less);
+                }
+            }
+            '>' => {
+                if chars.next_if_eq(&'=').is_some() {
+                    tokens.push(GreaterEqual);
+                } else {
+                    tokens.push(Greater);
+                }
+            }
+            '&' => {
+                if chars.next_if_eq(&'&').is_some() {
+                    tokens.push(AmpAmp);
+                } else {
+                    return Err(Error::msg("expected '&&'"));
+                }
+            }
+            '|' => {
+                if chars.next_if_eq(&'|').is_some() {
+                    tokens.push(PipePipe);
+                } else {
+                    return Err(Error::msg("expected '||'"));
+                }
+            }
+            ';' => tokens.push(Semicolon),
+            ',' => tokens.push(Comma),
             '(' | '[' => tokens.push(LeftParen),
             ')' | ']' => tokens.push(RightParen),
             d if d.is_ascii_digit() => {
-                let mut num = d.to_string();
-                while let Some(n_char) = chars.peek() {
-                    if n_char.is_ascii_digit() || n_char == &'.' {
-                        num.push(*n_char);
-                        chars.next();
-                    } else {
-                        break;
-                    }
-                }
-
-                tokens.push(Number(num.parse::<f32>()?));
+                tokens.push(Number(lex_number(d, &mut chars)?));
             }
             c if c.is_ascii_alphanumeric() => {
                 let mut string = c.to_string();
@@ -207,17 +646,8 @@ pub fn produce_tokens(expr: String) -> Result<Vec<Token>> {
                 }
 
                 match string.as_str() {
-                    "ln" => tokens.push(Ln),
-                    "sin" => tokens.push(Sin),
-                    "cos" => tokens.push(Cos),
-                    "tan" => tokens.push(Tan),
-                    "log" => tokens.push(Log),
-                    _ => {
-                        if string.len() > 1 {
-                            return Err(Error::msg("Variable length cannot exceed 1"));
-                        }
-                        tokens.push(Variable(string))
-                    }
+                    "i" => tokens.push(Number(Complex64::i())),
+                    _ => tokens.push(Variable(string)),
                 }
             }
             ' ' | '\n' | '\r' => {}
@@ -228,36 +658,121 @@ pub fn produce_tokens(expr: String) -> Result<Vec<Token>> {
     Ok(tokens)
 }
 
-fn populate_variables(tokens: Vec<Token>) -> Result<Vec<Token>> {
-    let mut map: HashMap<String, f32> = HashMap::new();
-    let mut populated = vec![];
-    for token in tokens.iter() {
-        if let Token::Variable(identifer) = token {
-            let value = map.get(identifer);
-            if let Some(value) = value {
-                populated.push(Token::Number(*value));
-            } else {
-                let mut var_value = String::new();
-                print!("Set value [{}]: ", identifer);
-                stdout().flush()?;
-                std::io::stdin().read_line(&mut var_value)?;
-                let parsed = var_value.trim().parse::<f32>()?;
-                map.insert(identifer.to_string(), parsed);
-                populated.push(Token::Number(parsed))
+/// Lexes a numeric literal starting at `first` (already consumed from
+/// `chars`): a `0x`/`0b`-prefixed integer, or a plain decimal with an
+/// optional `.`. Any run of digits may use `_` as a visual separator, as
+/// long as it doesn't sit at the start/end of the run or next to another `_`.
+fn lex_number(first: char, chars: &mut Peekable<std::str::Chars>) -> Result<Complex64> {
+    if first == '0' {
+        let radix = match chars.peek() {
+            Some('x') => Some(16u32),
+            Some('b') => Some(2u32),
+            _ => None,
+        };
+        if let Some(radix) = radix {
+            chars.next();
+            let mut digits = String::new();
+            while let Some(n_char) = chars.peek() {
+                if n_char.is_digit(radix) || n_char == &'_' {
+                    digits.push(*n_char);
+                    chars.next();
+                } else {
+                    break;
+                }
             }
+            let cleaned = strip_separators(&digits)?;
+            if cleaned.is_empty() {
+                return Err(Error::msg("numeric literal has no digits after prefix"));
+            }
+            let value = i64::from_str_radix(&cleaned, radix)?;
+            return Ok(Complex64::new(value as f64, 0.0));
+        }
+    }
+
+    let mut num = first.to_string();
+    while let Some(n_char) = chars.peek() {
+        if n_char.is_ascii_digit() || n_char == &'.' || n_char == &'_' {
+            num.push(*n_char);
+            chars.next();
         } else {
-            populated.push(token.clone())
+            break;
         }
     }
+    let cleaned = strip_separators(&num)?;
+    Ok(Complex64::new(cleaned.parse::<f64>()?, 0.0))
+}
 
-    Ok(populated)
+/// Strips `_` digit separators from a literal, rejecting one at either end,
+/// doubled up, or butted against the decimal point — any of which would
+/// otherwise silently collapse to nothing or a misleading position.
+fn strip_separators(literal: &str) -> Result<String> {
+    if literal.starts_with('_')
+        || literal.ends_with('_')
+        || literal.contains("__")
+        || literal.contains("_.")
+        || literal.contains("._")
+    {
+        return Err(Error::msg(format!(
+            "stray separator in numeric literal '{}'",
+            literal
+        )));
+    }
+    Ok(literal.chars().filter(|c| *c != '_').collect())
 }
-fn evaluate(expr: String) -> Result<f32> {
+
+/// Formats a complex result as `a+bi`, dropping the imaginary term when it's
+/// close enough to zero to be a float-precision artifact.
+fn format_complex(value: Complex64) -> String {
+    const EPSILON: f64 = 1e-9;
+    if value.im.abs() < EPSILON {
+        return format!("{}", value.re);
+    }
+    if value.im.is_sign_negative() {
+        format!("{}{}i", value.re, value.im)
+    } else {
+        format!("{}+{}i", value.re, value.im)
+    }
+}
+
+fn format_value(value: Value) -> String {
+    match value {
+        Value::Num(n) => format_complex(n),
+        Value::Bool(b) => b.to_string(),
+    }
+}
+
+fn evaluate(expr: String, env: &mut Environment) -> Result<Value> {
     let tokens = produce_tokens(expr)?;
-    let populated = populate_variables(tokens)?;
+    let stmts = parse_program(tokens.into_iter())?;
 
-    let result = ParseExpr::expr(&mut populated.into_iter().peekable())?.eval()?;
-    Ok(result)
+    let mut result = None;
+    for stmt in &stmts {
+        result = Some(stmt.eval(env)?);
+    }
+    result.ok_or_else(|| Error::msg("empty program"))
+}
+
+/// Reads one expression per line, evaluating it against a long-lived
+/// `Environment` so variable bindings carry over between lines. Evaluation
+/// errors are printed and the session continues; EOF (Ctrl-D) or Ctrl-C ends
+/// it, and any other line-editor error is returned.
+fn run_repl() -> Result<()> {
+    let mut env = Environment::new();
+    let mut editor = DefaultEditor::new()?;
+    loop {
+        match editor.readline(">> ") {
+            Ok(line) => {
+                editor.add_history_entry(line.as_str())?;
+                match evaluate(line, &mut env) {
+                    Ok(result) => println!("{}", format_value(result)),
+                    Err(err) => println!("Error: {}", err),
+                }
+            }
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
 }
 
 fn main() -> Result<()> {
@@ -266,49 +781,143 @@ fn main() -> Result<()> {
         let filepath = &args[1];
         if std::path::Path::new(filepath).exists() {
             let contents = fs::read_to_string(filepath)?;
-            let result = evaluate(contents)?;
+            let mut env = Environment::new();
+            let result = evaluate(contents, &mut env)?;
 
-            println!("[Result] {}", result);
+            println!("[Result] {}", format_value(result));
         } else {
             println!("file does not exist. usage: cord [filename]")
         }
     } else {
-        println!("usage: cord [filename]")
+        run_repl()?;
     }
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::evaluate;
+    use super::{evaluate, Environment, Value};
+    use num_complex::Complex64;
+
+    fn num(re: f64) -> Complex64 {
+        Complex64::new(re, 0.0)
+    }
+
+    fn real(re: f64) -> Value {
+        Value::Num(num(re))
+    }
+
+    fn eval(expr: &str) -> super::Result<Value> {
+        evaluate(expr.to_string(), &mut Environment::new())
+    }
+
     #[test]
     fn test_complex_arithmetic_expressions() {
-        assert_eq!(evaluate("(3 * 4) + (2 * 5) - 6".to_string()).unwrap(), 16.0);
-        assert_eq!(
-            evaluate("((10 + 5) * 2 - 3) / 4".to_string()).unwrap(),
-            6.75
-        );
-        assert_eq!(evaluate("3 + 4 * 2 / ( 1 - 5 )".to_string()).unwrap(), 1.0);
+        assert_eq!(eval("(3 * 4) + (2 * 5) - 6").unwrap(), real(16.0));
+        assert_eq!(eval("((10 + 5) * 2 - 3) / 4").unwrap(), real(6.75));
+        assert_eq!(eval("3 + 4 * 2 / ( 1 - 5 )").unwrap(), real(1.0));
+    }
+
+    #[test]
+    fn test_exact_integer_powers() {
+        assert_eq!(eval("2^3").unwrap(), real(8.0));
+        assert_eq!(eval("10^2").unwrap(), real(100.0));
+        assert_eq!(eval("5^3").unwrap(), real(125.0));
+        assert_eq!(eval("9^0.5").unwrap(), real(3.0));
     }
 
     #[test]
     fn test_nested_expressions() {
-        assert_eq!(evaluate("(2 + 3) * (4 - 1)".to_string()).unwrap(), 15.0);
-        assert_eq!(evaluate("10 + (5 * (3 - 1))".to_string()).unwrap(), 20.0);
+        assert_eq!(eval("(2 + 3) * (4 - 1)").unwrap(), real(15.0));
+        assert_eq!(eval("10 + (5 * (3 - 1))").unwrap(), real(20.0));
     }
 
     #[test]
     fn test_trigonometric_expressions() {
+        let result = eval("(3 * 4) + sin(45)").unwrap().as_num().unwrap();
+        assert!((result.re - 12.850_903_524_534_116).abs() < 1e-9);
+        assert_eq!(eval("cos(0) + (2 * 3)").unwrap(), real(7.0));
+    }
+
+    #[test]
+    fn test_error_cases() {
+        assert!(eval("invalid expression").is_err());
+        assert!(eval("").is_err());
+    }
+
+    #[test]
+    fn test_imaginary_unit_and_negative_roots() {
+        assert_eq!(eval("i * i").unwrap(), real(-1.0));
+        let ln_neg_one = eval("ln(-1)").unwrap().as_num().unwrap();
+        assert!((ln_neg_one.im - std::f64::consts::PI).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_environment_persists_across_evaluations() {
+        let mut env = Environment::new();
+        assert_eq!(evaluate("x = 3".to_string(), &mut env).unwrap(), real(3.0));
+        assert_eq!(evaluate("x^2".to_string(), &mut env).unwrap(), real(9.0));
+    }
+
+    #[test]
+    fn test_assignment_and_multi_statement_programs() {
+        let mut env = Environment::new();
+        assert_eq!(
+            evaluate("x = 3; x^2".to_string(), &mut env).unwrap(),
+            real(9.0),
+        );
         assert_eq!(
-            evaluate("(3 * 4) + sin(45)".to_string()).unwrap(),
-            12.8509035
+            evaluate("y = x + 1; y * 2".to_string(), &mut env).unwrap(),
+            real(8.0)
         );
-        assert_eq!(evaluate("cos(0) + (2 * 3)".to_string()).unwrap(), 7.0);
     }
 
     #[test]
-    fn test_error_cases() {
-        assert!(evaluate("invalid expression".to_string()).is_err());
-        assert!(evaluate("".to_string()).is_err());
+    fn test_user_defined_functions() {
+        let mut env = Environment::new();
+        assert_eq!(
+            evaluate("f(x, y) = x^2 + y; f(3, 4)".to_string(), &mut env).unwrap(),
+            real(13.0),
+        );
+        assert!(evaluate("f(1)".to_string(), &mut env).is_err());
+        assert!(evaluate("g(1, 2)".to_string(), &mut env).is_err());
+    }
+
+    #[test]
+    fn test_comparisons_and_boolean_operators() {
+        assert_eq!(eval("3 < 4").unwrap(), Value::Bool(true));
+        assert_eq!(eval("3 >= 4").unwrap(), Value::Bool(false));
+        assert_eq!(eval("3 == 3 && 1 != 2").unwrap(), Value::Bool(true));
+        assert_eq!(eval("3 == 4 || 1 == 1").unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_if_conditional() {
+        assert_eq!(eval("if(3 < 4, 1, 2)").unwrap(), real(1.0));
+        assert_eq!(eval("if(3 > 4, 1, 2)").unwrap(), real(2.0));
+    }
+
+    #[test]
+    fn test_if_cannot_be_redefined() {
+        let mut env = Environment::new();
+        assert!(evaluate("if(x) = x".to_string(), &mut env).is_err());
+        // The reserved ternary must still work after the rejected redefinition.
+        assert_eq!(
+            evaluate("if(3 < 4, 1, 2)".to_string(), &mut env).unwrap(),
+            real(1.0)
+        );
+    }
+
+    #[test]
+    fn test_hex_binary_and_underscore_literals() {
+        assert_eq!(eval("0xFF").unwrap(), real(255.0));
+        assert_eq!(eval("0b1010").unwrap(), real(10.0));
+        assert_eq!(eval("1_000_000").unwrap(), real(1_000_000.0));
+        assert_eq!(eval("0xFF_FF").unwrap(), real(65535.0));
+        assert!(eval("0x").is_err());
+        assert!(eval("1_").is_err());
+        assert!(eval("1__000").is_err());
+        assert!(eval("1._5").is_err());
+        assert!(eval("1_.5").is_err());
     }
 }